@@ -14,6 +14,7 @@ use std::{
         Hash,
         Hasher,
     },
+    sync::atomic::AtomicUsize,
 };
 
 /// Gets the name of the current or given function as a [`StubbyName`]
@@ -32,7 +33,7 @@ use std::{
 ///   shouldn't be doing this anyway) - the parent method's name will be
 ///   taken/used
 #[allow(unused_macros)]
-#[cfg_attr(not(feature = "type-safe"), macro_export)]
+#[macro_export]
 macro_rules! fn_name {
     () => {{
         // Hack from https://docs.rs/stdext/0.2.1/src/stdext/macros.rs.html#61-72
@@ -73,6 +74,44 @@ macro_rules! fn_name {
     }};
 }
 
+/// Rewrites a method (or every method in an `impl` block, when applied there
+/// instead) to check for a stub at the top of its body, equivalent to
+/// inserting [`stub_if_found!`] by hand
+///
+/// Takes an optional `field = <expr>` argument naming the [`StubbyState`]
+/// field to check, defaulting to `self.0`
+///
+/// Each rewritten method looks itself up via [`fn_name!`] internally, so the
+/// name it registers under is always exactly the one `insert`/`insert_when`/
+/// `call_count`/etc. compute for the same method - applying `#[stubbable]` to
+/// a single method or to the whole enclosing `impl` block produces identically
+/// qualified names either way
+///
+/// The injected check forwards the method's non-`self` arguments into
+/// [`get_with`](StubbyState::get_with), so [`insert_when`](StubbyState::insert_when)/
+/// [`insert_match`](StubbyState::insert_match) can dispatch on them - but
+/// `get_with` requires those arguments to be owned/`'static` (see its
+/// Limitations section). A method with a borrowed parameter (`&str`, `&[T]`,
+/// `&U`, ...) still compiles, but falls back to a plain [`stub_if_found!`]-
+/// style check with no argument matching for that method
+///
+/// ```no_run
+/// # use stubby::*;
+/// struct FizzBuzzer(StubbyState);
+///
+/// #[stubbable]
+/// impl FizzBuzzer {
+///     fn start(&self) -> String {
+///         String::from("this if no stub provided")
+///     }
+/// }
+/// ```
+///
+/// See `tests/stubbable.rs` for a full round-trip example that inserts a
+/// stub and asserts the stubbed value comes back out
+#[cfg(feature = "macros")]
+pub use stubby_macros::stubbable;
+
 /// Use at the start of a method to return a stub when in `#[cfg(test)]`, **if
 /// one is found**. If no stub is set, then the method executes normally
 ///
@@ -121,6 +160,20 @@ macro_rules! stub_if_found {
             }
         }
     };
+    // Forwards the call-site arguments, for use with `insert_when`/`insert_match`
+    ($mock:expr, $args:expr) => {
+        #[cfg(test)]
+        {
+            #[cfg(not(debug_assertions))]
+            compile_error!(
+                "stubby does not work in release mode, do not run tests with \
+                 --release"
+            );
+            if let Some(t) = $mock.get_with($crate::fn_name!(), $args) {
+                return t;
+            }
+        }
+    };
 }
 
 /// Use at the start of a method to return a stub when in `#[cfg(test)]`
@@ -178,6 +231,25 @@ macro_rules! stub {
             }
         }
     };
+    // Forwards the call-site arguments, for use with `insert_when`/`insert_match`
+    ($mock:expr, $args:expr) => {
+        #[cfg(test)]
+        {
+            #[cfg(not(debug_assertions))]
+            compile_error!(
+                "stubby does not work in release mode, do not run tests with \
+                 --release"
+            );
+            let name = $crate::fn_name!();
+            // Add always-true condition so that IDEs don't realise the return
+            // is unconditional (IntelliJ)
+            if true {
+                return $mock.get_with(name, $args).unwrap_or_else(|| {
+                    panic!("no stub configured for {name}")
+                });
+            }
+        }
+    };
 }
 
 /// An interned string type for holding function names. Returned by [`fn_name!`]
@@ -202,12 +274,85 @@ impl fmt::Display for StubbyName {
 }
 
 #[cfg(debug_assertions)]
-type StubbyFunction = Box<dyn Fn() -> Box<dyn std::any::Any> + Send + Sync>;
+type StubbyFunction = Box<dyn Fn() -> Option<Box<dyn std::any::Any>> + Send + Sync>;
+
+/// How many times a stubbed method is expected to be called, for use with
+/// [`StubbyState::expect`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Times {
+    /// Must be called exactly this many times
+    Exactly(usize),
+    /// Must be called at least this many times
+    AtLeast(usize),
+    /// Must be called at most this many times
+    AtMost(usize),
+    /// Must never be called
+    Never,
+}
+
+impl Times {
+    fn is_met_by(self, calls: usize) -> bool {
+        match self {
+            Times::Exactly(n) => calls == n,
+            Times::AtLeast(n) => calls >= n,
+            Times::AtMost(n) => calls <= n,
+            Times::Never => calls == 0,
+        }
+    }
+}
+
+impl fmt::Display for Times {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Times::Exactly(n) => write!(f, "exactly {n} time(s)"),
+            Times::AtLeast(n) => write!(f, "at least {n} time(s)"),
+            Times::AtMost(n) => write!(f, "at most {n} time(s)"),
+            Times::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// The stub registered for a single [`StubbyName`], plus bookkeeping for
+/// [`StubbyState::call_count`] et al.
+#[cfg(debug_assertions)]
+struct StubbyEntry {
+    func: StubbyFunction,
+    calls: AtomicUsize,
+}
+
+#[cfg(debug_assertions)]
+impl StubbyEntry {
+    fn new(func: StubbyFunction) -> Self {
+        StubbyEntry {
+            func,
+            calls: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A single argument-conditional stub registered via `insert_when`/
+/// `insert_match`. Takes the call-site arguments erased as `&dyn Any`
+/// (downcast back to the real `Args` tuple by the closure itself), and
+/// returns the stubbed value if it matches
+#[cfg(debug_assertions)]
+type StubbyMatcher =
+    Box<dyn Fn(&dyn std::any::Any) -> Option<Box<dyn std::any::Any>> + Send + Sync>;
 
 #[cfg(not(debug_assertions))]
 type StubbyStateInner = ();
 #[cfg(debug_assertions)]
-type StubbyStateInner = std::collections::BTreeMap<StubbyName, StubbyFunction>;
+#[derive(Default)]
+struct StubbyStateInner {
+    entries: std::collections::BTreeMap<StubbyName, StubbyEntry>,
+    expectations: std::collections::BTreeMap<StubbyName, Times>,
+    matchers: std::collections::BTreeMap<StubbyName, Vec<StubbyMatcher>>,
+    /// Call counters for names stubbed only via `insert_when`/`insert_match`,
+    /// i.e. that may have no [`StubbyEntry`] of their own. Seeded by
+    /// `insert_when`/`insert_match`, incremented by `get_with` on a matcher
+    /// hit, and folded into [`StubbyState::call_count_by_name`] alongside the
+    /// counters on `entries`
+    match_calls: std::collections::BTreeMap<StubbyName, AtomicUsize>,
+}
 
 /// Stores stub information. Initialise with [`StubbyState::new`] or
 /// [`StubbyState::default`]
@@ -299,7 +444,9 @@ impl StubbyState {
         name: StubbyName,
         obj: T,
     ) {
-        self.0.insert(name, cloneable_into_stubby_function(obj));
+        self.0
+            .entries
+            .insert(name, StubbyEntry::new(cloneable_into_stubby_function(obj)));
     }
 
     /// Adds a new function to be stubbed with the given `obj`. Repeated
@@ -329,8 +476,10 @@ impl StubbyState {
         F::Output: Clone + Send + Sync + 'static,
         Args: std::marker::Tuple,
     {
-        self.0
-            .insert(fn_name!(func), cloneable_into_stubby_function(obj));
+        self.0.entries.insert(
+            fn_name!(func),
+            StubbyEntry::new(cloneable_into_stubby_function(obj)),
+        );
     }
 
     #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
@@ -388,7 +537,10 @@ impl StubbyState {
         name: StubbyName,
         func: impl Fn() -> T + Send + Sync + 'static,
     ) {
-        self.0.insert(name, Box::new(move || Box::new(func())));
+        self.0.entries.insert(
+            name,
+            StubbyEntry::new(Box::new(move || Some(Box::new(func())))),
+        );
     }
 
     /// Adds a new function to be stubbed using the given function/closure
@@ -429,8 +581,10 @@ impl StubbyState {
         F::Output: 'static,
         Args: std::marker::Tuple,
     {
-        self.0
-            .insert(fn_name!(func), Box::new(move || Box::new(closure())));
+        self.0.entries.insert(
+            fn_name!(func),
+            StubbyEntry::new(Box::new(move || Some(Box::new(closure())))),
+        );
     }
 
     #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
@@ -457,91 +611,923 @@ impl StubbyState {
         panic!("should not have stubs being used outside of #[cfg(test)]");
     }
 
-    /// Fetches the value stored in the `StubbyState` for the given name
+    /// Adds a new function to be stubbed with a sequence of `values`, one of
+    /// which is returned (in order) on each call. Repeated `insert_seq`s will
+    /// overwrite existing entries, resetting the sequence
     ///
-    /// Usually you won't need to call this function directly, instead
-    /// preferring [`stub_if_found!`] or [`stub!`]
+    /// Once `values` has been exhausted, further calls behave as though no
+    /// stub had been set at all: [`stub_if_found!`] falls through to the
+    /// method's real implementation, and [`stub!`] panics
     ///
-    /// # Panics
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Counter(StubbyState);
     ///
-    /// If the `name` isn't stored, or if the value associated with `name` isn't
-    /// a `T`
-    #[cfg(debug_assertions)]
-    pub fn get<T: 'static>(&self, name: StubbyName) -> Option<T> {
-        self.0.get(&name).map(|stubby_fn: &StubbyFunction| {
-            *stubby_fn().downcast::<T>().unwrap_or_else(|_| {
-                panic!("incorrect type supplied for {name}")
-            })
-        })
+    /// impl Counter {
+    ///     fn next(&self) -> u32 {
+    ///         stub!(&self.0);
+    ///         0
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_seq(fn_name!(Counter::next), vec![1u32, 2, 3]);
+    /// ```
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn insert_seq<T: Clone + Send + Sync + 'static>(
+        &mut self,
+        name: StubbyName,
+        values: Vec<T>,
+    ) {
+        self.0
+            .entries
+            .insert(name, StubbyEntry::new(sequence_into_stubby_function(values)));
     }
 
-    #[cfg(not(debug_assertions))]
+    /// Adds a new function to be stubbed with a sequence of `values`, one of
+    /// which is returned (in order) on each call. Repeated `insert_seq`s will
+    /// overwrite existing entries, resetting the sequence
+    ///
+    /// Once `values` has been exhausted, further calls behave as though no
+    /// stub had been set at all: [`stub_if_found!`] falls through to the
+    /// method's real implementation, and [`stub!`] panics
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Counter(StubbyState);
+    ///
+    /// impl Counter {
+    ///     fn next(&self) -> u32 {
+    ///         stub!(&self.0);
+    ///         0
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_seq(Counter::next, vec![1u32, 2, 3]);
+    /// ```
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn insert_seq<Args, F>(&mut self, func: F, values: Vec<F::Output>)
+    where
+        F: FnOnce<Args>,
+        F::Output: Clone + Send + Sync + 'static,
+        Args: std::marker::Tuple,
+    {
+        self.0.entries.insert(
+            fn_name!(func),
+            StubbyEntry::new(sequence_into_stubby_function(values)),
+        );
+    }
+
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused_variables)]
+    pub fn insert_seq<T: Clone + Send + Sync + 'static>(
+        &mut self,
+        name: StubbyName,
+        values: Vec<T>,
+    ) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
     #[allow(unused)]
-    pub fn get<T: 'static>(&self, name: StubbyName) -> Option<T> {
+    pub fn insert_seq<Args, F>(&mut self, func: F, values: Vec<F::Output>)
+    where
+        F: FnOnce<Args>,
+        F::Output: Clone + Send + Sync + 'static,
+        Args: std::marker::Tuple,
+    {
         panic!("should not have stubs being used outside of #[cfg(test)]");
     }
-}
 
-impl fmt::Debug for StubbyState {
-    #[cfg(not(debug_assertions))]
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_tuple("StubbyState").finish()
+    /// Adds a stub for `name` (which must return a `Result<T, E>`) that
+    /// always succeeds with `value`. Builds on [`insert_with`](StubbyState::insert_with) - if you
+    /// don't need the `Result` wrapping handled for you, insert the `Ok`
+    /// yourself with [`insert`](StubbyState::insert) instead
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn parse(&self, raw: &str) -> Result<i32, String> {
+    ///         stub!(&self.0);
+    ///         raw.parse().map_err(|_| String::from("bad input"))
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_ok::<_, String>(fn_name!(Foo::parse), 4);
+    /// ```
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn insert_ok<T: Clone + Send + Sync + 'static, E: Send + Sync + 'static>(
+        &mut self,
+        name: StubbyName,
+        value: T,
+    ) {
+        self.insert_with::<Result<T, E>>(name, move || Ok(value.clone()));
     }
 
-    #[cfg(debug_assertions)]
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // StubbyFunction is !Debug, so just substitute it for a fixed
-        // string It's not like I had any useful type
-        // information anyway lol
-        let inner_debug = self
-            .0
-            .keys()
-            .map(|name| (name, "StubbyFunction"))
-            .collect::<std::collections::BTreeMap<_, _>>();
-        f.debug_tuple("StubbyState").field(&inner_debug).finish()
+    /// Adds a stub for `func` (which must return a `Result<T, E>`) that
+    /// always succeeds with `value`. Builds on [`insert_with`](StubbyState::insert_with) - if you
+    /// don't need the `Result` wrapping handled for you, insert the `Ok`
+    /// yourself with [`insert`](StubbyState::insert) instead
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn parse(&self, raw: &str) -> Result<i32, String> {
+    ///         stub!(&self.0);
+    ///         raw.parse().map_err(|_| String::from("bad input"))
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_ok(Foo::parse, 4);
+    /// ```
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn insert_ok<Args, F, T, E>(&mut self, func: F, value: T)
+    where
+        F: FnOnce<Args, Output = Result<T, E>>,
+        T: Clone + Send + Sync + 'static,
+        E: Send + Sync + 'static,
+        Args: std::marker::Tuple,
+    {
+        self.insert_with(func, move || Ok(value.clone()));
     }
-}
 
-impl Clone for StubbyState {
-    /// Returns an empty `StubbyState`
-    fn clone(&self) -> Self {
-        StubbyState::default()
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused)]
+    pub fn insert_ok<T: Clone + Send + Sync + 'static, E: Send + Sync + 'static>(
+        &mut self,
+        name: StubbyName,
+        value: T,
+    ) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
     }
-}
 
-impl PartialEq for StubbyState {
-    /// Always returns true
-    fn eq(&self, _other: &Self) -> bool {
-        true
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn insert_ok<Args, F, T, E>(&mut self, func: F, value: T)
+    where
+        F: FnOnce<Args, Output = Result<T, E>>,
+        T: Clone + Send + Sync + 'static,
+        E: Send + Sync + 'static,
+        Args: std::marker::Tuple,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
     }
-}
 
-impl Eq for StubbyState {}
+    /// Adds a stub for `name` (which must return a `Result<T, E>`) that
+    /// always fails with `error`. Builds on [`insert_with`](StubbyState::insert_with) - if you don't
+    /// need the `Result` wrapping handled for you, insert the `Err` yourself
+    /// with [`insert`](StubbyState::insert) instead
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn parse(&self, raw: &str) -> Result<i32, String> {
+    ///         stub!(&self.0);
+    ///         raw.parse().map_err(|_| String::from("bad input"))
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_err::<i32, _>(fn_name!(Foo::parse), String::from("nope"));
+    /// ```
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn insert_err<T: Send + Sync + 'static, E: Clone + Send + Sync + 'static>(
+        &mut self,
+        name: StubbyName,
+        error: E,
+    ) {
+        self.insert_with::<Result<T, E>>(name, move || Err(error.clone()));
+    }
 
-impl Hash for StubbyState {
-    /// Fixed hash
-    fn hash<H: Hasher>(&self, _state: &mut H) {}
-}
+    /// Adds a stub for `func` (which must return a `Result<T, E>`) that
+    /// always fails with `error`. Builds on [`insert_with`](StubbyState::insert_with) - if you don't
+    /// need the `Result` wrapping handled for you, insert the `Err` yourself
+    /// with [`insert`](StubbyState::insert) instead
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn parse(&self, raw: &str) -> Result<i32, String> {
+    ///         stub!(&self.0);
+    ///         raw.parse().map_err(|_| String::from("bad input"))
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_err(Foo::parse, String::from("nope"));
+    /// ```
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn insert_err<Args, F, T, E>(&mut self, func: F, error: E)
+    where
+        F: FnOnce<Args, Output = Result<T, E>>,
+        T: Send + Sync + 'static,
+        E: Clone + Send + Sync + 'static,
+        Args: std::marker::Tuple,
+    {
+        self.insert_with(func, move || Err(error.clone()));
+    }
 
-impl PartialOrd for StubbyState {
-    /// `StubbyState`s are always equal in order
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused)]
+    pub fn insert_err<T: Send + Sync + 'static, E: Clone + Send + Sync + 'static>(
+        &mut self,
+        name: StubbyName,
+        error: E,
+    ) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
     }
-}
 
-impl Ord for StubbyState {
-    /// `StubbyState`s are always equal in order
-    fn cmp(&self, _other: &Self) -> Ordering {
-        Ordering::Equal
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn insert_err<Args, F, T, E>(&mut self, func: F, error: E)
+    where
+        F: FnOnce<Args, Output = Result<T, E>>,
+        T: Send + Sync + 'static,
+        E: Clone + Send + Sync + 'static,
+        Args: std::marker::Tuple,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
     }
-}
 
-#[cfg(debug_assertions)]
-fn cloneable_into_stubby_function<T: Clone + Send + Sync + 'static>(
-    obj: T,
-) -> StubbyFunction {
-    Box::new(move || Box::new(obj.clone()))
+    /// Adds a stub for `name` that panics with `msg` whenever it's hit,
+    /// rather than returning a value. Builds on [`insert_with`](StubbyState::insert_with) and is
+    /// intended for exercising panic paths with `#[should_panic]` or
+    /// `catch_unwind`
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn risky(&self) -> i32 {
+    ///         stub!(&self.0);
+    ///         42
+    ///     }
+    /// }
+    ///
+    /// #[test]
+    /// #[should_panic(expected = "boom")]
+    /// fn risky_panics() {
+    ///     let mut stubs = StubbyState::new();
+    ///     stubs.insert_panic::<i32>(fn_name!(Foo::risky), "boom");
+    ///     Foo(stubs).risky();
+    /// }
+    /// ```
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn insert_panic<T: 'static>(&mut self, name: StubbyName, msg: impl Into<String>) {
+        let msg = msg.into();
+        self.insert_with::<T>(name, move || panic!("{msg}"));
+    }
+
+    /// Adds a stub for `func` that panics with `msg` whenever it's hit,
+    /// rather than returning a value. Builds on [`insert_with`](StubbyState::insert_with) and is
+    /// intended for exercising panic paths with `#[should_panic]` or
+    /// `catch_unwind`
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn risky(&self) -> i32 {
+    ///         stub!(&self.0);
+    ///         42
+    ///     }
+    /// }
+    ///
+    /// #[test]
+    /// #[should_panic(expected = "boom")]
+    /// fn risky_panics() {
+    ///     let mut stubs = StubbyState::new();
+    ///     stubs.insert_panic(Foo::risky, "boom");
+    ///     Foo(stubs).risky();
+    /// }
+    /// ```
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn insert_panic<Args, F>(&mut self, func: F, msg: impl Into<String>)
+    where
+        F: FnOnce<Args>,
+        F::Output: 'static,
+        Args: std::marker::Tuple,
+    {
+        let msg = msg.into();
+        self.insert_with(func, move || panic!("{msg}"));
+    }
+
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused)]
+    pub fn insert_panic<T: 'static>(&mut self, name: StubbyName, msg: impl Into<String>) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn insert_panic<Args, F>(&mut self, func: F, msg: impl Into<String>)
+    where
+        F: FnOnce<Args>,
+        F::Output: 'static,
+        Args: std::marker::Tuple,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Adds a stub for `func` that only applies when `predicate` returns
+    /// `true` for the arguments it was actually called with. Stubs are tried
+    /// in the order they were inserted, and the first matching one wins; if
+    /// none match, `func` falls back to its plain [`insert`](StubbyState::insert)
+    /// stub (if any) or its real implementation
+    ///
+    /// `MatchArgs` is independent of `func`'s own argument tuple (which, for
+    /// methods, includes `self`) - it's simply whatever
+    /// [`stub!`]/[`stub_if_found!`] are called with at the call site, e.g.
+    /// `stub!(&self.0, (n,))` to match against `predicate: impl Fn(&(u32,))`
+    ///
+    /// See [`get_with`](StubbyState::get_with)'s Limitations section -
+    /// `MatchArgs` must be an owned, `'static` type, so this can't match on
+    /// borrowed parameters like `&str`
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn double(&self, n: u32) -> u32 {
+    ///         stub!(&self.0, (n,));
+    ///         n * 2
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_when(Foo::double, |(n,): &(u32,)| *n == 4, 100);
+    /// ```
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn insert_when<FnArgs, F, MatchArgs>(
+        &mut self,
+        func: F,
+        predicate: impl Fn(&MatchArgs) -> bool + Send + Sync + 'static,
+        obj: F::Output,
+    ) where
+        F: FnOnce<FnArgs>,
+        F::Output: Clone + Send + Sync + 'static,
+        FnArgs: std::marker::Tuple,
+        MatchArgs: 'static,
+    {
+        let matcher: StubbyMatcher = Box::new(move |args: &dyn std::any::Any| {
+            let args = args
+                .downcast_ref::<MatchArgs>()
+                .expect("argument type mismatch passed to get_with");
+            predicate(args).then(|| Box::new(obj.clone()) as Box<dyn std::any::Any>)
+        });
+        let name = fn_name!(func);
+        self.0.matchers.entry(name).or_default().push(matcher);
+        self.0.match_calls.entry(name).or_insert_with(|| AtomicUsize::new(0));
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn insert_when<FnArgs, F, MatchArgs>(
+        &mut self,
+        func: F,
+        predicate: impl Fn(&MatchArgs) -> bool + Send + Sync + 'static,
+        obj: F::Output,
+    ) where
+        F: FnOnce<FnArgs>,
+        F::Output: Clone + Send + Sync + 'static,
+        FnArgs: std::marker::Tuple,
+        MatchArgs: 'static,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Adds a stub for `func` whose response is computed by `matcher`,
+    /// called with the actual arguments on every invocation. Returning `None`
+    /// tries the next registered matcher (if any), falling back to `func`'s
+    /// plain [`insert`](StubbyState::insert) stub (if any) or its real
+    /// implementation
+    ///
+    /// `MatchArgs` is independent of `func`'s own argument tuple (which, for
+    /// methods, includes `self`) - it's simply whatever
+    /// [`stub!`]/[`stub_if_found!`] are called with at the call site, e.g.
+    /// `stub!(&self.0, (n,))` to match against `matcher: impl Fn((u32,))`
+    ///
+    /// See [`get_with`](StubbyState::get_with)'s Limitations section -
+    /// `MatchArgs` must be an owned, `'static` type, so this can't match on
+    /// borrowed parameters like `&str`
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn double(&self, n: u32) -> u32 {
+    ///         stub!(&self.0, (n,));
+    ///         n * 2
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert_match(Foo::double, |(n,): (u32,)| (n > 10).then_some(0));
+    /// ```
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn insert_match<FnArgs, F, MatchArgs>(
+        &mut self,
+        func: F,
+        matcher: impl Fn(MatchArgs) -> Option<F::Output> + Send + Sync + 'static,
+    ) where
+        F: FnOnce<FnArgs>,
+        F::Output: 'static,
+        FnArgs: std::marker::Tuple,
+        MatchArgs: Clone + 'static,
+    {
+        let matcher: StubbyMatcher = Box::new(move |args: &dyn std::any::Any| {
+            let args = args
+                .downcast_ref::<MatchArgs>()
+                .expect("argument type mismatch passed to get_with")
+                .clone();
+            matcher(args).map(|val| Box::new(val) as Box<dyn std::any::Any>)
+        });
+        let name = fn_name!(func);
+        self.0.matchers.entry(name).or_default().push(matcher);
+        self.0.match_calls.entry(name).or_insert_with(|| AtomicUsize::new(0));
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn insert_match<FnArgs, F, MatchArgs>(
+        &mut self,
+        func: F,
+        matcher: impl Fn(MatchArgs) -> Option<F::Output> + Send + Sync + 'static,
+    ) where
+        F: FnOnce<FnArgs>,
+        F::Output: 'static,
+        FnArgs: std::marker::Tuple,
+        MatchArgs: Clone + 'static,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Adds a stub for `name` whose response is computed by `matcher`, called
+    /// with the call-site arguments erased to `&dyn Any` on every invocation
+    /// (downcast it back to the real argument tuple yourself). Returning
+    /// `None` tries the next registered matcher (if any), falling back to
+    /// `name`'s plain [`insert`](StubbyState::insert) stub (if any) or its
+    /// real implementation
+    ///
+    /// For the ergonomic, typed version, see
+    /// [`insert_match`](StubbyState::insert_match) under the `type-safe`
+    /// feature
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn insert_match(
+        &mut self,
+        name: StubbyName,
+        matcher: impl Fn(&dyn std::any::Any) -> Option<Box<dyn std::any::Any>>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.0.matchers.entry(name).or_default().push(Box::new(matcher));
+        self.0.match_calls.entry(name).or_insert_with(|| AtomicUsize::new(0));
+    }
+
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused)]
+    pub fn insert_match(
+        &mut self,
+        name: StubbyName,
+        matcher: impl Fn(&dyn std::any::Any) -> Option<Box<dyn std::any::Any>>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Fetches the value stored in the `StubbyState` for the given name
+    ///
+    /// Usually you won't need to call this function directly, instead
+    /// preferring [`stub_if_found!`] or [`stub!`]
+    ///
+    /// Returns `None` if `name` isn't stored, or if its sequence (see
+    /// [`insert_seq`](StubbyState::insert_seq)) has been exhausted
+    ///
+    /// # Panics
+    ///
+    /// If the value associated with `name` isn't a `T`
+    #[cfg(debug_assertions)]
+    pub fn get<T: 'static>(&self, name: StubbyName) -> Option<T> {
+        self.0.entries.get(&name).and_then(|entry| {
+            entry.calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            (entry.func)().map(|val| {
+                *val.downcast::<T>().unwrap_or_else(|_| {
+                    panic!("incorrect type supplied for {name}")
+                })
+            })
+        })
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[allow(unused)]
+    pub fn get<T: 'static>(&self, name: StubbyName) -> Option<T> {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Like [`get`](StubbyState::get), but first tries the argument-
+    /// conditional stubs registered for `name` via
+    /// [`insert_when`](StubbyState::insert_when)/[`insert_match`](StubbyState::insert_match),
+    /// in the order they were inserted. Falls back to `get`'s plain stub (if
+    /// any) when no matcher matches
+    ///
+    /// Usually you won't need to call this function directly, instead
+    /// preferring [`stub_if_found!`] or [`stub!`] with a second argument
+    ///
+    /// # Limitations
+    ///
+    /// `Args` is matched by erasing it to `&dyn Any`, which requires `Args:
+    /// 'static` - so `args` (and therefore every argument forwarded by
+    /// [`stub!`]/[`stub_if_found!`] at the call site) must be an owned,
+    /// `'static` value. Borrowed parameters (`&str`, `&[T]`, `&U`, ...) can't
+    /// be forwarded as-is; clone/own them first (e.g. `name.to_owned()`) if
+    /// you need to match on them
+    ///
+    /// # Panics
+    ///
+    /// If a matcher's or the fallback stub's value isn't a `T`
+    #[cfg(debug_assertions)]
+    pub fn get_with<Args: 'static, T: 'static>(&self, name: StubbyName, args: Args) -> Option<T> {
+        if let Some(matchers) = self.0.matchers.get(&name) {
+            let args: &dyn std::any::Any = &args;
+            for matcher in matchers {
+                if let Some(val) = matcher(args) {
+                    if let Some(calls) = self.0.match_calls.get(&name) {
+                        calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    return Some(*val.downcast::<T>().unwrap_or_else(|_| {
+                        panic!("incorrect type supplied for {name}")
+                    }));
+                }
+            }
+        }
+        self.get(name)
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[allow(unused)]
+    pub fn get_with<Args, T: 'static>(&self, name: StubbyName, args: Args) -> Option<T> {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    #[cfg(debug_assertions)]
+    fn call_count_by_name(&self, name: StubbyName) -> usize {
+        let entry_calls = self
+            .0
+            .entries
+            .get(&name)
+            .map(|entry| entry.calls.load(std::sync::atomic::Ordering::Relaxed))
+            .unwrap_or(0);
+        let match_calls = self
+            .0
+            .match_calls
+            .get(&name)
+            .map(|calls| calls.load(std::sync::atomic::Ordering::Relaxed))
+            .unwrap_or(0);
+        entry_calls + match_calls
+    }
+
+    /// Returns the number of times the stub for `name` has been looked up
+    /// (i.e. how many times [`stub!`]/[`stub_if_found!`] hit it), regardless
+    /// of what was returned. Returns `0` if no stub was ever inserted for
+    /// `name`
+    ///
+    /// Lookups through [`get_with`](StubbyState::get_with) count once a
+    /// matcher matches or a plain stub is found; a lookup where no matcher
+    /// matches and no plain stub exists is not counted
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn call_count(&self, name: StubbyName) -> usize {
+        self.call_count_by_name(name)
+    }
+
+    /// Returns the number of times the stub for `func` has been looked up
+    /// (i.e. how many times [`stub!`]/[`stub_if_found!`] hit it), regardless
+    /// of what was returned. Returns `0` if no stub was ever inserted for
+    /// `func`
+    ///
+    /// Lookups through [`get_with`](StubbyState::get_with) count once a
+    /// matcher matches or a plain stub is found; a lookup where no matcher
+    /// matches and no plain stub exists is not counted
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn call_count<Args, F>(&self, func: F) -> usize
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        self.call_count_by_name(fn_name!(func))
+    }
+
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused_variables)]
+    pub fn call_count(&self, name: StubbyName) -> usize {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn call_count<Args, F>(&self, func: F) -> usize
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Returns whether the stub for `name` has been looked up at least once.
+    /// Shorthand for `self.call_count(name) > 0`
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn was_called(&self, name: StubbyName) -> bool {
+        self.call_count(name) > 0
+    }
+
+    /// Returns whether the stub for `func` has been looked up at least once.
+    /// Shorthand for `self.call_count(func) > 0`
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn was_called<Args, F>(&self, func: F) -> bool
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        self.call_count_by_name(fn_name!(func)) > 0
+    }
+
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused_variables)]
+    pub fn was_called(&self, name: StubbyName) -> bool {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn was_called<Args, F>(&self, func: F) -> bool
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Asserts that the stub for `name` was called exactly `expected` times
+    ///
+    /// # Panics
+    ///
+    /// If the actual call count doesn't match `expected`
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn verify(&self, name: StubbyName, expected: usize) {
+        let actual = self.call_count(name);
+        assert_eq!(
+            actual, expected,
+            "expected {name} to be called exactly {expected} time(s), but it \
+             was called {actual} time(s)"
+        );
+    }
+
+    /// Asserts that the stub for `func` was called exactly `expected` times
+    ///
+    /// # Panics
+    ///
+    /// If the actual call count doesn't match `expected`
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn verify<Args, F>(&self, func: F, expected: usize)
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        let name = fn_name!(func);
+        let actual = self.call_count_by_name(name);
+        assert_eq!(
+            actual, expected,
+            "expected {name} to be called exactly {expected} time(s), but it \
+             was called {actual} time(s)"
+        );
+    }
+
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused_variables)]
+    pub fn verify(&self, name: StubbyName, expected: usize) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn verify<Args, F>(&self, func: F, expected: usize)
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Registers an expectation that the stub for `name` is called a given
+    /// number of times, checked by [`verify_all`](StubbyState::verify_all) or
+    /// when this `StubbyState` is dropped
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn return_four(&self) -> u32 {
+    ///         stub!(&self.0);
+    ///         4
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert(fn_name!(Foo::return_four), 4u32);
+    /// stubs.expect(fn_name!(Foo::return_four), Times::Exactly(1));
+    /// ```
+    #[cfg(all(debug_assertions, not(feature = "type-safe")))]
+    pub fn expect(&mut self, name: StubbyName, times: Times) {
+        self.0.expectations.insert(name, times);
+    }
+
+    /// Registers an expectation that the stub for `func` is called a given
+    /// number of times, checked by [`verify_all`](StubbyState::verify_all) or
+    /// when this `StubbyState` is dropped
+    ///
+    /// ```no_run
+    /// # use stubby::*;
+    /// struct Foo(StubbyState);
+    ///
+    /// impl Foo {
+    ///     fn return_four(&self) -> u32 {
+    ///         stub!(&self.0);
+    ///         4
+    ///     }
+    /// }
+    ///
+    /// let mut stubs = StubbyState::new();
+    /// stubs.insert(Foo::return_four, 4u32);
+    /// stubs.expect(Foo::return_four, Times::Exactly(1));
+    /// ```
+    #[cfg(all(debug_assertions, feature = "type-safe"))]
+    pub fn expect<Args, F>(&mut self, func: F, times: Times)
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        self.0.expectations.insert(fn_name!(func), times);
+    }
+
+    #[cfg(all(not(debug_assertions), not(feature = "type-safe")))]
+    #[allow(unused_variables)]
+    pub fn expect(&mut self, name: StubbyName, times: Times) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    #[cfg(all(not(debug_assertions), feature = "type-safe"))]
+    #[allow(unused)]
+    pub fn expect<Args, F>(&mut self, func: F, times: Times)
+    where
+        F: FnOnce<Args>,
+        Args: std::marker::Tuple,
+    {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+
+    /// Checks every expectation registered via [`expect`](StubbyState::expect)
+    /// against the actual call counts recorded so far
+    ///
+    /// # Panics
+    ///
+    /// If any expectation isn't met. The panic message lists every failing
+    /// expectation, not just the first
+    #[cfg(debug_assertions)]
+    pub fn verify_all(&self) {
+        let failures = self
+            .0
+            .expectations
+            .iter()
+            .filter_map(|(name, times)| {
+                let actual = self.call_count_by_name(*name);
+                if times.is_met_by(actual) {
+                    None
+                } else {
+                    Some(format!(
+                        "expected {name} to be called {times}, but it was \
+                         called {actual} time(s)"
+                    ))
+                }
+            })
+            .collect::<Vec<_>>();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[allow(unused)]
+    pub fn verify_all(&self) {
+        panic!("should not have stubs being used outside of #[cfg(test)]");
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Drop for StubbyState {
+    /// Runs [`verify_all`](StubbyState::verify_all), so that unmet
+    /// [`expect`](StubbyState::expect)ations are caught even if you forget to
+    /// call it yourself. Skipped while already unwinding from a panic, so it
+    /// doesn't obscure the original failure
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.verify_all();
+        }
+    }
+}
+
+impl fmt::Debug for StubbyState {
+    #[cfg(not(debug_assertions))]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("StubbyState").finish()
+    }
+
+    #[cfg(debug_assertions)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // StubbyFunction is !Debug, so just substitute it for a fixed
+        // string It's not like I had any useful type
+        // information anyway lol
+        let inner_debug = self
+            .0
+            .entries
+            .keys()
+            .map(|name| (name, "StubbyFunction"))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        f.debug_tuple("StubbyState").field(&inner_debug).finish()
+    }
+}
+
+impl Clone for StubbyState {
+    /// Returns an empty `StubbyState`
+    fn clone(&self) -> Self {
+        StubbyState::default()
+    }
+}
+
+impl PartialEq for StubbyState {
+    /// Always returns true
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for StubbyState {}
+
+impl Hash for StubbyState {
+    /// Fixed hash
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl PartialOrd for StubbyState {
+    /// `StubbyState`s are always equal in order
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StubbyState {
+    /// `StubbyState`s are always equal in order
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+#[cfg(debug_assertions)]
+fn cloneable_into_stubby_function<T: Clone + Send + Sync + 'static>(
+    obj: T,
+) -> StubbyFunction {
+    Box::new(move || Some(Box::new(obj.clone())))
+}
+
+/// Builds a [`StubbyFunction`] that yields each element of `values` in turn
+/// on successive calls, and `None` once they've all been given out
+#[cfg(debug_assertions)]
+fn sequence_into_stubby_function<T: Clone + Send + Sync + 'static>(
+    values: Vec<T>,
+) -> StubbyFunction {
+    let idx = AtomicUsize::new(0);
+    Box::new(move || {
+        let i = idx.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        values
+            .get(i)
+            .cloned()
+            .map(|val| Box::new(val) as Box<dyn std::any::Any>)
+    })
 }
 
 #[cfg(all(test, not(feature = "type-safe")))]
@@ -562,6 +1548,120 @@ mod tests {
         stubby.get::<NotClone>(StubbyName::default()).unwrap();
     }
 
+    #[test]
+    fn insert_seq_exhausts() {
+        let mut stubby = StubbyState::new();
+        stubby.insert_seq(StubbyName::default(), vec![1, 2, 3]);
+
+        assert_eq!(stubby.get::<i32>(StubbyName::default()), Some(1));
+        assert_eq!(stubby.get::<i32>(StubbyName::default()), Some(2));
+        assert_eq!(stubby.get::<i32>(StubbyName::default()), Some(3));
+        assert_eq!(stubby.get::<i32>(StubbyName::default()), None);
+    }
+
+    #[test]
+    fn insert_ok_and_insert_err() {
+        let mut stubby = StubbyState::new();
+        stubby.insert_ok::<i32, String>(StubbyName::default(), 4);
+        assert_eq!(
+            stubby.get::<Result<i32, String>>(StubbyName::default()),
+            Some(Ok(4))
+        );
+
+        stubby.insert_err::<i32, String>(StubbyName::default(), String::from("nope"));
+        assert_eq!(
+            stubby.get::<Result<i32, String>>(StubbyName::default()),
+            Some(Err(String::from("nope")))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn insert_panic_panics_on_call() {
+        let mut stubby = StubbyState::new();
+        stubby.insert_panic::<i32>(StubbyName::default(), "boom");
+        stubby.get::<i32>(StubbyName::default());
+    }
+
+    #[test]
+    fn insert_match_dispatches_by_argument() {
+        let mut stubby = StubbyState::new();
+        stubby.insert_match(StubbyName::default(), |args: &dyn std::any::Any| {
+            let (n,) = args.downcast_ref::<(i32,)>().unwrap();
+            (*n == 4).then(|| Box::new(100) as Box<dyn std::any::Any>)
+        });
+        stubby.insert(StubbyName::default(), -1);
+
+        assert_eq!(
+            stubby.get_with::<(i32,), i32>(StubbyName::default(), (4,)),
+            Some(100)
+        );
+        assert_eq!(
+            stubby.get_with::<(i32,), i32>(StubbyName::default(), (5,)),
+            Some(-1)
+        );
+    }
+
+    #[test]
+    fn get_with_matcher_hits_count_towards_call_count() {
+        let mut stubby = StubbyState::new();
+        // No plain `insert` for this name - only a matcher - so `call_count`
+        // has nothing but `match_calls` to draw on
+        stubby.insert_match(StubbyName::default(), |_: &dyn std::any::Any| {
+            Some(Box::new(1) as Box<dyn std::any::Any>)
+        });
+
+        assert_eq!(stubby.call_count(StubbyName::default()), 0);
+
+        stubby.get_with::<(i32,), i32>(StubbyName::default(), (4,));
+        stubby.get_with::<(i32,), i32>(StubbyName::default(), (5,));
+
+        assert_eq!(stubby.call_count(StubbyName::default()), 2);
+        assert!(stubby.was_called(StubbyName::default()));
+        stubby.verify(StubbyName::default(), 2);
+    }
+
+    #[test]
+    fn call_count_tracks_hits() {
+        let mut stubby = StubbyState::new();
+        stubby.insert(StubbyName::default(), 4);
+
+        assert_eq!(stubby.call_count(StubbyName::default()), 0);
+        assert!(!stubby.was_called(StubbyName::default()));
+
+        stubby.get::<i32>(StubbyName::default());
+        stubby.get::<i32>(StubbyName::default());
+
+        assert_eq!(stubby.call_count(StubbyName::default()), 2);
+        assert!(stubby.was_called(StubbyName::default()));
+        stubby.verify(StubbyName::default(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "called exactly 1 time(s)")]
+    fn verify_panics_on_mismatch() {
+        let stubby = StubbyState::new();
+        stubby.verify(StubbyName::default(), 1);
+    }
+
+    #[test]
+    fn verify_all_checks_every_expectation() {
+        let mut stubby = StubbyState::new();
+        stubby.insert(StubbyName::default(), 4);
+        stubby.expect(StubbyName::default(), Times::AtLeast(1));
+
+        stubby.get::<i32>(StubbyName::default());
+        stubby.verify_all();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected")]
+    fn drop_verifies_unmet_expectations() {
+        let mut stubby = StubbyState::new();
+        stubby.expect(StubbyName::default(), Times::Exactly(1));
+        drop(stubby);
+    }
+
     #[test]
     fn is_send_and_sync() {
         fn f<T: Send + Sync>(_: T) {}
@@ -604,3 +1704,173 @@ mod tests {
         assert_eq!(fn_name!(f), f().await);
     }
 }
+
+#[cfg(all(test, feature = "type-safe"))]
+mod type_safe_tests {
+    use super::*;
+
+    #[test]
+    fn insert_seq_exhausts() {
+        fn target() -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.insert_seq(target, vec![1, 2, 3]);
+
+        assert_eq!(stubby.get::<i32>(fn_name!(target)), Some(1));
+        assert_eq!(stubby.get::<i32>(fn_name!(target)), Some(2));
+        assert_eq!(stubby.get::<i32>(fn_name!(target)), Some(3));
+        assert_eq!(stubby.get::<i32>(fn_name!(target)), None);
+    }
+
+    #[test]
+    fn call_count_tracks_hits() {
+        fn target() -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.insert(target, 4);
+
+        assert_eq!(stubby.call_count(target), 0);
+        assert!(!stubby.was_called(target));
+
+        stubby.get::<i32>(fn_name!(target));
+        stubby.get::<i32>(fn_name!(target));
+
+        assert_eq!(stubby.call_count(target), 2);
+        assert!(stubby.was_called(target));
+        stubby.verify(target, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "called exactly 1 time(s)")]
+    fn verify_panics_on_mismatch() {
+        fn target() -> i32 {
+            unreachable!()
+        }
+
+        let stubby = StubbyState::new();
+        stubby.verify(target, 1);
+    }
+
+    #[test]
+    fn verify_all_checks_every_expectation() {
+        fn target() -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.insert(target, 4);
+        stubby.expect(target, Times::AtLeast(1));
+
+        stubby.get::<i32>(fn_name!(target));
+        stubby.verify_all();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected")]
+    fn drop_verifies_unmet_expectations() {
+        fn target() -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.expect(target, Times::Exactly(1));
+        drop(stubby);
+    }
+
+    #[test]
+    fn insert_when_dispatches_by_argument() {
+        fn target(_n: i32) -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.insert_when(target, |(n,): &(i32,)| *n == 4, 100);
+        stubby.insert(target, -1);
+
+        assert_eq!(
+            stubby.get_with::<(i32,), i32>(fn_name!(target), (4,)),
+            Some(100)
+        );
+        assert_eq!(
+            stubby.get_with::<(i32,), i32>(fn_name!(target), (5,)),
+            Some(-1)
+        );
+    }
+
+    #[test]
+    fn insert_match_dispatches_by_argument() {
+        fn target(_n: i32) -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.insert_match(target, |(n,): (i32,)| (n == 4).then_some(100));
+        stubby.insert(target, -1);
+
+        assert_eq!(
+            stubby.get_with::<(i32,), i32>(fn_name!(target), (4,)),
+            Some(100)
+        );
+        assert_eq!(
+            stubby.get_with::<(i32,), i32>(fn_name!(target), (5,)),
+            Some(-1)
+        );
+    }
+
+    #[test]
+    fn get_with_matcher_hits_count_towards_call_count() {
+        fn target(_n: i32) -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        // No plain `insert` for this name - only a matcher - so `call_count`
+        // has nothing but `match_calls` to draw on
+        stubby.insert_when(target, |_: &(i32,)| true, 1);
+
+        assert_eq!(stubby.call_count(target), 0);
+
+        stubby.get_with::<(i32,), i32>(fn_name!(target), (4,));
+        stubby.get_with::<(i32,), i32>(fn_name!(target), (5,));
+
+        assert_eq!(stubby.call_count(target), 2);
+        assert!(stubby.was_called(target));
+        stubby.verify(target, 2);
+    }
+
+    #[test]
+    fn insert_ok_and_insert_err() {
+        fn target() -> Result<i32, String> {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.insert_ok(target, 4);
+        assert_eq!(
+            stubby.get::<Result<i32, String>>(fn_name!(target)),
+            Some(Ok(4))
+        );
+
+        stubby.insert_err(target, String::from("nope"));
+        assert_eq!(
+            stubby.get::<Result<i32, String>>(fn_name!(target)),
+            Some(Err(String::from("nope")))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn insert_panic_panics_on_call() {
+        fn target() -> i32 {
+            unreachable!()
+        }
+
+        let mut stubby = StubbyState::new();
+        stubby.insert_panic(target, "boom");
+        stubby.get::<i32>(fn_name!(target));
+    }
+}
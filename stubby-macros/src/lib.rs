@@ -0,0 +1,137 @@
+//! Proc-macro implementation for `stubby`'s `#[stubbable]` attribute. See the
+//! `stubby` crate's documentation for usage - this crate only exists to host
+//! the proc-macro, and isn't meant to be depended on directly.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, parse_quote,
+    spanned::Spanned,
+    FnArg, ImplItem, ImplItemFn, ItemImpl, Pat, Signature, Stmt, Token,
+};
+
+/// The arguments to `#[stubbable(...)]`: currently just an optional
+/// `field = <expr>` naming the `StubbyState` field to check, defaulting to
+/// `self.0`
+struct StubbableArgs {
+    field: syn::Expr,
+}
+
+impl Parse for StubbableArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() {
+            return Ok(StubbableArgs {
+                field: parse_quote!(self.0),
+            });
+        }
+        let key: syn::Ident = input.parse()?;
+        if key != "field" {
+            return Err(syn::Error::new(key.span(), "expected `field = <expr>`"));
+        }
+        input.parse::<Token![=]>()?;
+        Ok(StubbableArgs {
+            field: input.parse()?,
+        })
+    }
+}
+
+#[proc_macro_attribute]
+pub fn stubbable(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as StubbableArgs);
+
+    if let Ok(imp) = syn::parse::<ItemImpl>(item.clone()) {
+        return stubbable_impl(args, imp);
+    }
+    match syn::parse::<ImplItemFn>(item) {
+        Ok(mut method) => {
+            inject_stub_check(&args.field, &method.sig.clone(), &mut method.block.stmts);
+            quote!(#method).into()
+        }
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn stubbable_impl(args: StubbableArgs, mut imp: ItemImpl) -> TokenStream {
+    for item in &mut imp.items {
+        if let ImplItem::Fn(method) = item {
+            inject_stub_check(&args.field, &method.sig.clone(), &mut method.block.stmts);
+        }
+    }
+    quote!(#imp).into()
+}
+
+/// Prepends the [`stub_if_found!`]-equivalent check to `stmts`: if no stub is
+/// found, the method body runs as normal
+///
+/// The stub name is derived by calling [`fn_name!`](https://docs.rs/stubby)
+/// from within the method body itself (the same trick [`stub_if_found!`]/
+/// [`stub!`] use), rather than formatting one from the `syn` path - that
+/// guarantees it's always identical to the name `insert`/`insert_when`/
+/// `call_count`/etc. compute for the same function, however it's qualified
+fn inject_stub_check(field: &syn::Expr, sig: &Signature, stmts: &mut Vec<Stmt>) {
+    let args = match call_site_args(sig) {
+        Ok(args) => args,
+        Err(err) => {
+            stmts.insert(0, Stmt::Expr(syn::Expr::Verbatim(err.to_compile_error()), None));
+            return;
+        }
+    };
+    // Matching on the call-site arguments requires them to be owned/`'static`
+    // (see `StubbyState::get_with`'s Limitations section) - for methods that
+    // take a borrowed parameter, fall back to a plain, argument-less check
+    // instead of forwarding arguments that can't satisfy that bound
+    let lookup = match args {
+        Some(args) => quote! {
+            #field.get_with(::stubby::fn_name!(), (#(#args,)*))
+        },
+        None => quote! {
+            #field.get(::stubby::fn_name!())
+        },
+    };
+    let check: Stmt = parse_quote! {
+        #[cfg(test)]
+        {
+            #[cfg(not(debug_assertions))]
+            compile_error!(
+                "stubby does not work in release mode, do not run tests with --release"
+            );
+            if let Some(__stubby_ret) = #lookup {
+                return __stubby_ret;
+            }
+        }
+    };
+    stmts.insert(0, check);
+}
+
+/// The non-receiver arguments of `sig`, to forward into `get_with` as the
+/// match tuple (`self` is never matchable - see
+/// [`StubbyState::get_with`](https://docs.rs/stubby) for why). Returns `Ok(None)`
+/// if any parameter is a borrowed reference (`&str`, `&[T]`, `&U`, ...),
+/// since those can't satisfy `get_with`'s `'static` bound - callers should
+/// fall back to an argument-less check in that case
+fn call_site_args(sig: &Signature) -> syn::Result<Option<Vec<syn::Expr>>> {
+    let mut args = Vec::new();
+    for arg in &sig.inputs {
+        let pat_type = match arg {
+            FnArg::Receiver(_) => continue,
+            FnArg::Typed(pat_type) => pat_type,
+        };
+        if matches!(*pat_type.ty, syn::Type::Reference(_)) {
+            return Ok(None);
+        }
+        match &*pat_type.pat {
+            Pat::Ident(pat_ident) => {
+                let ident = &pat_ident.ident;
+                args.push(parse_quote!(#ident));
+            }
+            other => {
+                return Err(syn::Error::new(
+                    other.span(),
+                    "#[stubbable] only supports plain identifier arguments, not patterns",
+                ));
+            }
+        }
+    }
+    Ok(Some(args))
+}
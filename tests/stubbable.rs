@@ -0,0 +1,94 @@
+//! Round-trip tests for `#[stubbable]`: insert a stub through the documented
+//! `insert`/`fn_name!` API and assert it's actually returned. Guards against
+//! the name the macro registers under drifting out of sync with the name
+//! `insert`/`call_count`/etc. compute for the same method.
+
+#![cfg(feature = "macros")]
+
+use stubby::*;
+
+struct FizzBuzzer(StubbyState);
+
+#[stubbable]
+impl FizzBuzzer {
+    fn start(&self) -> String {
+        String::from("not stubbed")
+    }
+
+    fn double(&self, n: u32) -> u32 {
+        n * 2
+    }
+
+    fn shout(&self, name: &str) -> String {
+        format!("HI {name}")
+    }
+}
+
+struct Lone(StubbyState);
+
+impl Lone {
+    #[stubbable]
+    fn solo(&self, n: u32) -> u32 {
+        n + 1
+    }
+}
+
+#[cfg(not(feature = "type-safe"))]
+mod not_type_safe {
+    use super::*;
+
+    #[test]
+    fn impl_level_stub_round_trips_and_falls_through() {
+        let mut state = StubbyState::new();
+        state.insert(fn_name!(FizzBuzzer::double), 999u32);
+        let buzzer = FizzBuzzer(state);
+        assert_eq!(buzzer.double(2), 999);
+        assert_eq!(buzzer.start(), "not stubbed");
+    }
+
+    #[test]
+    fn borrowed_parameter_falls_back_to_a_plain_check() {
+        let mut state = StubbyState::new();
+        state.insert(fn_name!(FizzBuzzer::shout), String::from("stubbed"));
+        let buzzer = FizzBuzzer(state);
+        assert_eq!(buzzer.shout("world"), "stubbed");
+    }
+
+    #[test]
+    fn method_level_attribute_qualifies_the_name_the_same_way() {
+        let mut state = StubbyState::new();
+        state.insert(fn_name!(Lone::solo), 42u32);
+        let lone = Lone(state);
+        assert_eq!(lone.solo(1), 42);
+    }
+}
+
+#[cfg(feature = "type-safe")]
+mod type_safe {
+    use super::*;
+
+    #[test]
+    fn impl_level_stub_round_trips_and_falls_through() {
+        let mut state = StubbyState::new();
+        state.insert(FizzBuzzer::double, 999u32);
+        let buzzer = FizzBuzzer(state);
+        assert_eq!(buzzer.double(2), 999);
+        assert_eq!(buzzer.start(), "not stubbed");
+    }
+
+    #[test]
+    fn borrowed_parameter_falls_back_to_a_plain_check() {
+        let mut state = StubbyState::new();
+        state.insert(FizzBuzzer::shout, String::from("stubbed"));
+        let buzzer = FizzBuzzer(state);
+        assert_eq!(buzzer.shout("world"), "stubbed");
+    }
+
+    #[test]
+    fn method_level_attribute_qualifies_the_name_the_same_way() {
+        let mut state = StubbyState::new();
+        state.insert(Lone::solo, 42u32);
+        let lone = Lone(state);
+        assert_eq!(lone.solo(1), 42);
+    }
+}